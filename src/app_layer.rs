@@ -0,0 +1,112 @@
+//! 可插拔的应用层协议识别框架：按注册顺序探测协议，谁先认领一条流就接管它。
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+/// 一条 TCP 流里字节流动的方向。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+/// 一条流的数据：两个方向各自独立的字节缓冲区，解析器从这里取字节、吃掉已经
+/// 处理过的部分。`client_addr`/`client_port` 是这条流里最先发出数据的一端，
+/// `server_addr`/`server_port` 是另一端，既用来在输出时查 DNS 解析到的域名，
+/// 也用来拼出结构化输出里的流五元组。
+pub struct StreamBuffer {
+    pub client_addr: IpAddr,
+    pub client_port: u16,
+    pub server_addr: IpAddr,
+    pub server_port: u16,
+    pub c2s: Vec<u8>,
+    pub s2c: Vec<u8>,
+}
+
+impl StreamBuffer {
+    pub fn new(
+        client_addr: IpAddr,
+        client_port: u16,
+        server_addr: IpAddr,
+        server_port: u16,
+    ) -> Self {
+        StreamBuffer {
+            client_addr,
+            client_port,
+            server_addr,
+            server_port,
+            c2s: Vec::new(),
+            s2c: Vec::new(),
+        }
+    }
+
+    pub fn buffer(&self, dir: Direction) -> &[u8] {
+        match dir {
+            Direction::ClientToServer => &self.c2s,
+            Direction::ServerToClient => &self.s2c,
+        }
+    }
+}
+
+/// 解析器处理完新到达字节之后的结果，目前只用来给调用方/日志提示；解析器
+/// 自己的 `parse` 方法内部应该循环吃掉所有能解析出来的消息，而不是一次只
+/// 处理一条就返回。
+#[derive(Debug, PartialEq, Eq)]
+pub enum ParseResult {
+    /// 至少解析/消费了一部分数据。
+    Consumed,
+    /// 现有数据不足以解析出下一条消息，原样留着等后面的包。
+    Incomplete,
+}
+
+/// 应用层协议解析器：先用 `probe` 认出协议，选中之后整条流的生命周期都由
+/// `parse` 反复调用来消费新到达的字节。
+pub trait AppLayerParser {
+    /// 解析器名字，只用来打日志/调试。
+    #[allow(dead_code)]
+    fn name(&self) -> &'static str;
+
+    /// 用某个方向最先攒到的字节判断是不是自己认识的协议。数据不够就返回
+    /// `false`，registry 会在下次有更多字节时再探测一次。
+    fn probe(&self, data: &[u8]) -> bool;
+
+    /// 消费 `stream` 里 `dir` 方向新到达的字节。
+    fn parse(
+        &mut self,
+        dir: Direction,
+        stream: &mut StreamBuffer,
+        dns_names: &HashMap<IpAddr, String>,
+    ) -> ParseResult;
+}
+
+/// 已注册的协议解析器工厂列表，按注册顺序依次探测。
+pub struct Registry {
+    factories: Vec<Box<dyn Fn() -> Box<dyn AppLayerParser>>>,
+}
+
+impl Registry {
+    /// `format` 只有 `HttpParser` 关心，会被捕获进它的工厂闭包里；其它协议的
+    /// 解析器（比如 `RedisParser`）目前还是固定的调试输出，不受 `--format` 影响。
+    pub fn new(format: crate::http::OutputFormat) -> Self {
+        Registry {
+            factories: vec![
+                Box::new(move || {
+                    Box::new(crate::http::HttpParser::new(format)) as Box<dyn AppLayerParser>
+                }),
+                Box::new(|| {
+                    Box::new(crate::redis::RedisParser::default()) as Box<dyn AppLayerParser>
+                }),
+            ],
+        }
+    }
+
+    /// 依次用每个已注册的解析器探测 `data`，返回第一个认领这条流的解析器实例；
+    /// 数据还不够判断时所有解析器都会返回 `false`，由调用方等下一批字节到达
+    /// 之后再试一次。
+    pub fn probe(&self, data: &[u8]) -> Option<Box<dyn AppLayerParser>> {
+        self.factories.iter().find_map(|make| {
+            let candidate = make();
+            candidate.probe(data).then_some(candidate)
+        })
+    }
+}
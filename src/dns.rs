@@ -0,0 +1,227 @@
+//! DNS 报文解析（UDP 53 端口流量），用于把解析到的域名关联到后续的 HTTP 连接上。
+
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+#[derive(Debug)]
+pub struct DnsPacket {
+    pub id: u16,
+    pub is_response: bool,
+    pub opcode: u8,
+    pub rcode: u8,
+    /// 头部里的 NSCOUNT/ARCOUNT：目前只读出来暴露给调用方，不展开解码
+    /// authority/additional 记录。
+    pub nscount: u16,
+    pub arcount: u16,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsRecord>,
+}
+
+#[derive(Debug)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub qtype: u16,
+}
+
+#[derive(Debug)]
+pub enum DnsRecordData {
+    A(Ipv4Addr),
+    Aaaa(Ipv6Addr),
+    Cname(String),
+    Ns(String),
+    Soa { mname: String, rname: String },
+    Other(u16),
+}
+
+#[derive(Debug)]
+pub struct DnsRecord {
+    pub name: String,
+    pub ttl: u32,
+    pub data: DnsRecordData,
+}
+
+/// 解析一个完整的 DNS 报文（UDP payload）。报文不足 12 字节头部，或者 question
+/// 段解码过程中越界，都当作“不是一个有效的 DNS 报文”返回 `None`；answer 段里
+/// 遇到第一条解不出来的资源记录就停下，返回的 `answers` 只含它之前已经解出的记录。
+pub fn parse(data: &[u8]) -> Option<DnsPacket> {
+    if data.len() < 12 {
+        return None;
+    }
+    let id = u16::from_be_bytes([data[0], data[1]]);
+    let flags = u16::from_be_bytes([data[2], data[3]]);
+    let is_response = flags & 0x8000 != 0;
+    let opcode = ((flags >> 11) & 0x0f) as u8;
+    let rcode = (flags & 0x0f) as u8;
+    let qdcount = u16::from_be_bytes([data[4], data[5]]) as usize;
+    let ancount = u16::from_be_bytes([data[6], data[7]]) as usize;
+    let nscount = u16::from_be_bytes([data[8], data[9]]);
+    let arcount = u16::from_be_bytes([data[10], data[11]]);
+
+    let mut offset = 12;
+    let mut questions = Vec::with_capacity(qdcount);
+    for _ in 0..qdcount {
+        let (name, next) = read_name(data, offset)?;
+        if data.len() < next + 4 {
+            return None;
+        }
+        let qtype = u16::from_be_bytes([data[next], data[next + 1]]);
+        offset = next + 4;
+        questions.push(DnsQuestion { name, qtype });
+    }
+
+    let mut answers = Vec::with_capacity(ancount);
+    for _ in 0..ancount {
+        let Some((name, next)) = read_name(data, offset) else {
+            break;
+        };
+        if data.len() < next + 10 {
+            break;
+        }
+        let rtype = u16::from_be_bytes([data[next], data[next + 1]]);
+        let ttl = u32::from_be_bytes([
+            data[next + 4],
+            data[next + 5],
+            data[next + 6],
+            data[next + 7],
+        ]);
+        let rdlength = u16::from_be_bytes([data[next + 8], data[next + 9]]) as usize;
+        let rdata_offset = next + 10;
+        if data.len() < rdata_offset + rdlength {
+            break;
+        }
+        let Some(record_data) = decode_rdata(data, rtype, rdata_offset, rdlength) else {
+            break;
+        };
+        answers.push(DnsRecord {
+            name,
+            ttl,
+            data: record_data,
+        });
+        offset = rdata_offset + rdlength;
+    }
+
+    Some(DnsPacket {
+        id,
+        is_response,
+        opcode,
+        rcode,
+        nscount,
+        arcount,
+        questions,
+        answers,
+    })
+}
+
+fn decode_rdata(
+    data: &[u8],
+    rtype: u16,
+    rdata_offset: usize,
+    rdlength: usize,
+) -> Option<DnsRecordData> {
+    Some(match rtype {
+        1 if rdlength == 4 => DnsRecordData::A(Ipv4Addr::new(
+            data[rdata_offset],
+            data[rdata_offset + 1],
+            data[rdata_offset + 2],
+            data[rdata_offset + 3],
+        )),
+        28 if rdlength == 16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(&data[rdata_offset..rdata_offset + 16]);
+            DnsRecordData::Aaaa(Ipv6Addr::from(octets))
+        }
+        5 => DnsRecordData::Cname(read_name(data, rdata_offset)?.0),
+        2 => DnsRecordData::Ns(read_name(data, rdata_offset)?.0),
+        6 => {
+            let (mname, after_mname) = read_name(data, rdata_offset)?;
+            let (rname, _) = read_name(data, after_mname)?;
+            DnsRecordData::Soa { mname, rname }
+        }
+        other => DnsRecordData::Other(other),
+    })
+}
+
+/// 解析从 `offset` 开始的一个 QNAME：标签序列，每个标签前面是一个长度字节，
+/// 遇到长度 `0x00` 结束；长度字节高两位为 `11`（即 `>= 0xC0`）表示压缩指针，
+/// 后续 14 位是报文内的绝对偏移，跳过去接着读，但返回的 `next` 偏移必须停在
+/// 第一次跳转之前的位置（指针本身占 2 字节），这样调用方才能正确跳过这个
+/// QNAME 继续解析报文的其余部分。
+fn read_name(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels = Vec::new();
+    let mut pos = offset;
+    let mut end_pos: Option<usize> = None;
+    let mut jumps = 0;
+
+    loop {
+        if jumps > 32 {
+            return None; // 压缩指针可能成环，给一个硬上限避免死循环
+        }
+        let len = *data.get(pos)?;
+        if len == 0 {
+            if end_pos.is_none() {
+                end_pos = Some(pos + 1);
+            }
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            let second = *data.get(pos + 1)? as usize;
+            if end_pos.is_none() {
+                end_pos = Some(pos + 2);
+            }
+            pos = (((len & 0x3f) as usize) << 8) | second;
+            jumps += 1;
+            continue;
+        }
+        let len = len as usize;
+        let start = pos + 1;
+        let label = data.get(start..start + len)?;
+        labels.push(String::from_utf8_lossy(label).to_string());
+        pos = start + len;
+    }
+
+    Some((labels.join("."), end_pos.unwrap_or(pos)))
+}
+
+pub fn record_type_name(qtype: u16) -> &'static str {
+    match qtype {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        28 => "AAAA",
+        _ => "?",
+    }
+}
+
+#[cfg(test)]
+mod read_name_tests {
+    use super::read_name;
+
+    #[test]
+    fn decodes_plain_labels() {
+        let mut data = vec![3, b'w', b'w', b'w', 3, b'c', b'o', b'm', 0];
+        data.push(0xff); // 后面还有别的数据，确认不会被多读
+        let (name, next) = read_name(&data, 0).unwrap();
+        assert_eq!(name, "www.com");
+        assert_eq!(next, 9);
+    }
+
+    #[test]
+    fn follows_compression_pointer() {
+        // offset 0: "example" 标签的完整定义
+        // offset 9: 一个指向 offset 0 的压缩指针
+        let mut data = vec![7, b'e', b'x', b'a', b'm', b'p', b'l', b'e', 0];
+        data.push(0xc0);
+        data.push(0x00);
+        let (name, next) = read_name(&data, 9).unwrap();
+        assert_eq!(name, "example");
+        // 返回的 next 必须停在指针之后（2 字节），而不是跳转目标那边
+        assert_eq!(next, 11);
+    }
+
+    #[test]
+    fn rejects_compression_pointer_cycle() {
+        // offset 0 是一个指向自己的压缩指针，构造出一个死循环
+        let data = [0xc0, 0x00];
+        assert!(read_name(&data, 0).is_none());
+    }
+}
@@ -0,0 +1,789 @@
+//! HTTP/1.x 请求-响应解析，实现 `AppLayerParser`，per-flow 状态放在 `HttpParser`
+//! 自己的字段里：请求恒从 `c2s` 解析、响应恒从 `s2c` 解析，用一个 FIFO 队列
+//! 把 keep-alive 流水线里的请求和响应配对起来。收到 `101` + `Upgrade: websocket`
+//! 响应之后，整条流的两个方向都改按 WebSocket 帧解析。
+//!
+//! 已知限制：SSE（`text/event-stream`）响应永远不会 `complete`，因为它本来就
+//! 没有结束标志，只会在连接关闭时结束。如果客户端在一条 SSE 连接还开着的时候
+//! 又在同一条连接上流水线发了下一个请求，那个请求的响应字节会被当成 SSE 的新
+//! 事件继续喂给队首这个事务，而不是作为独立事务被解析——这种流水线场景目前
+//! 不处理，也几乎不会在真实的 SSE 客户端里出现（打开 SSE 之后通常就不会再在
+//! 同一条连接上发别的请求了）。
+
+use crate::app_layer::{AppLayerParser, Direction, ParseResult, StreamBuffer};
+use clap::ValueEnum;
+use flate2::read::{DeflateDecoder, GzDecoder};
+use std::collections::{HashMap, VecDeque};
+use std::io::Read;
+use std::net::IpAddr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// 事务输出格式，对应 `--format` 命令行参数。`Pretty` 是终端里的彩色人类可读
+/// 格式（默认），`Json`/`Jsonl` 输出同一份数据的 JSON 表示，分别是带缩进的单个
+/// 对象和单行紧凑对象，方便接到 jq 或日志管道里，思路上类似 Suricata 的 eve.json。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Pretty,
+    Json,
+    Jsonl,
+}
+
+#[derive(PartialEq, Debug)]
+enum TransactionState {
+    RequestBody,
+    ResponseHeader,
+    ResponseBody,
+}
+
+/// `Content-Encoding` 取值，决定打印前该怎么解压 body。
+#[derive(PartialEq, Debug, Clone, Copy)]
+enum ContentEncoding {
+    Identity,
+    Gzip,
+    Deflate,
+    Brotli,
+}
+
+impl ContentEncoding {
+    fn from_header_value(val: &str) -> Self {
+        match val.trim().to_lowercase().as_str() {
+            "gzip" | "x-gzip" => ContentEncoding::Gzip,
+            "deflate" => ContentEncoding::Deflate,
+            "br" => ContentEncoding::Brotli,
+            _ => ContentEncoding::Identity,
+        }
+    }
+}
+
+struct HttpTransaction {
+    method: String,
+    path: String,
+    req_headers: HashMap<String, String>,
+    req_header: String,
+    req_body: Vec<u8>,
+    req_encoding: ContentEncoding,
+    expected_req_len: usize,
+    status: u16,
+    res_headers: HashMap<String, String>,
+    res_header: String,
+    res_body_raw: Vec<u8>,
+    res_body_events: Vec<String>,
+    res_events_emitted: usize,
+    res_encoding: ContentEncoding,
+    expected_res_len: usize,
+    is_sse: bool,
+    is_chunked: bool,
+    state: TransactionState,
+    req_printed: bool,
+}
+
+/// per-flow 状态：正在等请求体读完的半成品事务（尚未进入配对队列）、已经解析完
+/// 请求、按到达顺序排队等待匹配响应的事务（支持 keep-alive 流水线），以及一旦
+/// 握手升级成功之后接管整条流的 WebSocket 模式。`ws_*_fragments` 保存两个方向
+/// 各自尚未拼完的分片消息（`0x0` continuation 帧），直到收到 `FIN=1` 的帧。
+pub struct HttpParser {
+    format: OutputFormat,
+    in_progress_req: Option<HttpTransaction>,
+    pending: VecDeque<HttpTransaction>,
+    ws_mode: bool,
+    ws_c2s_fragments: Option<(u8, Vec<u8>)>,
+    ws_s2c_fragments: Option<(u8, Vec<u8>)>,
+}
+
+impl Default for HttpParser {
+    fn default() -> Self {
+        HttpParser::new(OutputFormat::default())
+    }
+}
+
+impl AppLayerParser for HttpParser {
+    fn name(&self) -> &'static str {
+        "http"
+    }
+
+    fn probe(&self, data: &[u8]) -> bool {
+        const METHODS: [&[u8]; 9] = [
+            b"GET ",
+            b"POST ",
+            b"PUT ",
+            b"DELETE ",
+            b"HEAD ",
+            b"OPTIONS ",
+            b"PATCH ",
+            b"CONNECT ",
+            b"TRACE ",
+        ];
+        METHODS.iter().any(|m| data.starts_with(m))
+    }
+
+    fn parse(
+        &mut self,
+        dir: Direction,
+        stream: &mut StreamBuffer,
+        dns_names: &HashMap<IpAddr, String>,
+    ) -> ParseResult {
+        if self.ws_mode {
+            return self.process_websocket(dir, stream);
+        }
+        match dir {
+            Direction::ClientToServer => self.process_c2s(stream),
+            Direction::ServerToClient => self.process_s2c(stream, dns_names),
+        }
+    }
+}
+
+impl HttpParser {
+    pub fn new(format: OutputFormat) -> Self {
+        HttpParser {
+            format,
+            in_progress_req: None,
+            pending: VecDeque::new(),
+            ws_mode: false,
+            ws_c2s_fragments: None,
+            ws_s2c_fragments: None,
+        }
+    }
+
+    /// 从 client->server 缓冲区里切出请求，解析完的请求进入 `pending` 队列排队
+    /// 等响应，不等待响应到达就继续尝试切下一条请求，这样 keep-alive 流水线里
+    /// 背靠背发出的多个请求都能被识别出来。
+    fn process_c2s(&mut self, stream: &mut StreamBuffer) -> ParseResult {
+        let mut progressed = false;
+        loop {
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+            let mut consumed = 0;
+
+            if let Some(tx) = &mut self.in_progress_req {
+                let take = std::cmp::min(tx.expected_req_len - tx.req_body.len(), stream.c2s.len());
+                tx.req_body.extend_from_slice(&stream.c2s[..take]);
+                consumed = take;
+                if tx.req_body.len() >= tx.expected_req_len {
+                    let mut tx = self.in_progress_req.take().unwrap();
+                    tx.state = TransactionState::ResponseHeader;
+                    self.pending.push_back(tx);
+                }
+            } else {
+                let mut req = httparse::Request::new(&mut headers);
+                if let Ok(httparse::Status::Complete(amt)) = req.parse(&stream.c2s) {
+                    let mut content_len = 0;
+                    let mut req_encoding = ContentEncoding::Identity;
+                    let mut h_str = String::new();
+                    let mut req_headers = HashMap::new();
+                    for h in req.headers.iter() {
+                        let name = h.name.to_lowercase();
+                        let val = String::from_utf8_lossy(h.value);
+                        if name == "content-length" {
+                            content_len = val.parse().unwrap_or(0);
+                        }
+                        if name == "content-encoding" {
+                            req_encoding = ContentEncoding::from_header_value(&val);
+                        }
+                        h_str.push_str(&format!("  {}: {}\n", h.name, val));
+                        req_headers.insert(name, val.to_string());
+                    }
+                    let method = req.method.unwrap_or("").to_string();
+                    let path = req.path.unwrap_or("").to_string();
+                    let mut tx = HttpTransaction {
+                        req_header: format!(
+                            "\x1b[1;32m▶ REQUEST: {} {}\x1b[0m\n{}",
+                            method, path, h_str
+                        ),
+                        method,
+                        path,
+                        req_headers,
+                        req_body: Vec::new(),
+                        req_encoding,
+                        expected_req_len: content_len,
+                        status: 0,
+                        res_headers: HashMap::new(),
+                        res_header: String::new(),
+                        res_body_raw: Vec::new(),
+                        res_body_events: Vec::new(),
+                        res_events_emitted: 0,
+                        res_encoding: ContentEncoding::Identity,
+                        expected_res_len: 0,
+                        is_sse: false,
+                        is_chunked: false,
+                        req_printed: false,
+                        state: TransactionState::RequestBody,
+                    };
+                    consumed = amt;
+                    if content_len > 0 {
+                        self.in_progress_req = Some(tx);
+                    } else {
+                        tx.state = TransactionState::ResponseHeader;
+                        self.pending.push_back(tx);
+                    }
+                }
+            }
+
+            if consumed > 0 {
+                stream.c2s.drain(..consumed);
+                progressed = true;
+            } else {
+                break;
+            }
+        }
+        if progressed {
+            ParseResult::Consumed
+        } else {
+            ParseResult::Incomplete
+        }
+    }
+
+    /// 从 server->client 缓冲区里解析响应，永远匹配 `pending` 队列最前面的那个
+    /// 请求（FIFO），这样同一条连接上排队的多个请求/响应也能按发出顺序正确
+    /// 配对。
+    fn process_s2c(
+        &mut self,
+        stream: &mut StreamBuffer,
+        dns_names: &HashMap<IpAddr, String>,
+    ) -> ParseResult {
+        let mut progressed = false;
+        let host = dns_names.get(&stream.server_addr).cloned();
+        let format = self.format;
+
+        loop {
+            let mut headers = [httparse::EMPTY_HEADER; 64];
+            let mut consumed = 0;
+            let mut complete = false;
+            let mut upgrade_to_ws = false;
+
+            if let Some(tx) = self.pending.front_mut() {
+                match tx.state {
+                    TransactionState::RequestBody => {
+                        unreachable!("只有请求已经解析完毕的事务才会进入 pending 队列")
+                    }
+                    TransactionState::ResponseHeader => {
+                        let mut res = httparse::Response::new(&mut headers);
+                        if let Ok(httparse::Status::Complete(amt)) = res.parse(&stream.s2c) {
+                            let mut clen = 0;
+                            let mut h_str = String::new();
+                            let mut is_ws_upgrade = false;
+                            for h in res.headers.iter() {
+                                let name = h.name.to_lowercase();
+                                let val = String::from_utf8_lossy(h.value);
+                                if name == "content-type" && val.contains("text/event-stream") {
+                                    tx.is_sse = true;
+                                }
+                                if name == "content-length" {
+                                    clen = val.parse().unwrap_or(0);
+                                }
+                                if name == "transfer-encoding"
+                                    && val.to_lowercase().contains("chunked")
+                                {
+                                    tx.is_chunked = true;
+                                }
+                                if name == "content-encoding" {
+                                    tx.res_encoding = ContentEncoding::from_header_value(&val);
+                                }
+                                if name == "upgrade" && val.to_lowercase().contains("websocket") {
+                                    is_ws_upgrade = true;
+                                }
+                                h_str.push_str(&format!("  {}: {}\n", h.name, val));
+                                tx.res_headers.insert(name, val.to_string());
+                            }
+                            tx.status = res.code.unwrap_or(0);
+                            tx.res_header = format!(
+                                "\x1b[1;34m◀ RESPONSE: {} {}\x1b[0m\n{}",
+                                tx.status,
+                                res.reason.unwrap_or(""),
+                                h_str
+                            );
+                            tx.expected_res_len = clen;
+                            tx.state = TransactionState::ResponseBody;
+                            consumed = amt;
+                            if tx.status == 101 && is_ws_upgrade {
+                                upgrade_to_ws = true;
+                                complete = true;
+                            } else if !tx.is_sse && !tx.is_chunked && tx.expected_res_len == 0 {
+                                complete = true;
+                            }
+                        }
+                    }
+                    TransactionState::ResponseBody => {
+                        if tx.is_chunked {
+                            let (amt, finished) = decode_chunked(&stream.s2c, &mut tx.res_body_raw);
+                            consumed = amt;
+                            if finished {
+                                complete = true;
+                            }
+                        } else if tx.is_sse {
+                            let body = String::from_utf8_lossy(&stream.s2c).to_string();
+                            let mut new_e = false;
+                            for e in body.split("\n\n") {
+                                if !e.trim().is_empty() {
+                                    tx.res_body_events.push(e.trim().to_string());
+                                    new_e = true;
+                                }
+                            }
+                            consumed = stream.s2c.len();
+                            if new_e {
+                                output_transaction(tx, stream, host.as_deref(), format);
+                            }
+                        } else {
+                            let take = std::cmp::min(
+                                tx.expected_res_len - tx.res_body_raw.len(),
+                                stream.s2c.len(),
+                            );
+                            tx.res_body_raw.extend_from_slice(&stream.s2c[..take]);
+                            consumed = take;
+                            if tx.res_body_raw.len() >= tx.expected_res_len {
+                                complete = true;
+                            }
+                        }
+                    }
+                }
+            }
+
+            if consumed > 0 {
+                stream.s2c.drain(..consumed);
+                progressed = true;
+            }
+            if complete {
+                if let Some(mut done) = self.pending.pop_front() {
+                    output_transaction(&mut done, stream, host.as_deref(), format);
+                }
+            }
+            if upgrade_to_ws {
+                println!("\x1b[1;35m[WebSocket] 握手完成，连接切换为 WebSocket 帧模式\x1b[0m");
+                self.ws_mode = true;
+            }
+            if consumed == 0 && !complete {
+                break;
+            }
+        }
+
+        if progressed {
+            ParseResult::Consumed
+        } else {
+            ParseResult::Incomplete
+        }
+    }
+
+    /// 握手升级成功之后接管整条流：反复从 `dir` 方向的缓冲区里切出完整的
+    /// RFC 6455 帧，continuation 帧（opcode `0x0`）按方向分别累积进
+    /// `ws_c2s_fragments`/`ws_s2c_fragments`，直到收到 `FIN=1` 才拼成完整消息
+    /// 打印出来。不完整的帧原样留在缓冲区里，等下一批字节到达再重试。
+    fn process_websocket(&mut self, dir: Direction, stream: &mut StreamBuffer) -> ParseResult {
+        let buf = match dir {
+            Direction::ClientToServer => &mut stream.c2s,
+            Direction::ServerToClient => &mut stream.s2c,
+        };
+        let fragments = match dir {
+            Direction::ClientToServer => &mut self.ws_c2s_fragments,
+            Direction::ServerToClient => &mut self.ws_s2c_fragments,
+        };
+
+        let mut progressed = false;
+        while let Some((frame, consumed)) = decode_ws_frame(buf) {
+            buf.drain(..consumed);
+            progressed = true;
+            match frame.opcode {
+                0x0 => {
+                    if let Some((_, payload)) = fragments.as_mut() {
+                        payload.extend_from_slice(&frame.payload);
+                    }
+                    if frame.fin {
+                        if let Some((initial_opcode, payload)) = fragments.take() {
+                            print_ws_message(dir, initial_opcode, &payload);
+                        }
+                    }
+                }
+                0x1 | 0x2 if !frame.fin => {
+                    *fragments = Some((frame.opcode, frame.payload));
+                }
+                other => print_ws_message(dir, other, &frame.payload),
+            }
+        }
+
+        if progressed {
+            ParseResult::Consumed
+        } else {
+            ParseResult::Incomplete
+        }
+    }
+}
+
+/// 一个已经完整解出来（去掉掩码）的 WebSocket 帧。
+struct WsFrame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// 尝试从 `data` 开头解析一个完整的 WebSocket 帧：第 1 字节 FIN+opcode，第 2
+/// 字节 MASK 位 + 7 位长度（`126`/`127` 表示长度另存在后面 2/8 字节大端整数里），
+/// 如果 MASK 位为 1 则紧跟 4 字节掩码键，负载用该键循环异或解掩码。数据不够就
+/// 返回 `None`，调用方把剩余字节原样留着等下一批数据到达再重试。
+fn decode_ws_frame(data: &[u8]) -> Option<(WsFrame, usize)> {
+    let &[b0, b1, ..] = data else { return None };
+    let fin = b0 & 0x80 != 0;
+    let opcode = b0 & 0x0f;
+    let masked = b1 & 0x80 != 0;
+    let len7 = (b1 & 0x7f) as usize;
+
+    let mut offset = 2;
+    let payload_len = match len7 {
+        126 => {
+            let bytes = data.get(offset..offset + 2)?;
+            offset += 2;
+            u16::from_be_bytes([bytes[0], bytes[1]]) as usize
+        }
+        127 => {
+            let bytes = data.get(offset..offset + 8)?;
+            offset += 8;
+            u64::from_be_bytes(bytes.try_into().ok()?) as usize
+        }
+        n => n,
+    };
+
+    let mask_key = if masked {
+        let key = data.get(offset..offset + 4)?;
+        offset += 4;
+        Some([key[0], key[1], key[2], key[3]])
+    } else {
+        None
+    };
+
+    let payload_end = offset.checked_add(payload_len)?;
+    let mut payload = data.get(offset..payload_end)?.to_vec();
+    if let Some(key) = mask_key {
+        for (i, b) in payload.iter_mut().enumerate() {
+            *b ^= key[i % 4];
+        }
+    }
+
+    Some((
+        WsFrame {
+            fin,
+            opcode,
+            payload,
+        },
+        payload_end,
+    ))
+}
+
+/// 打印一条已经拼完整的 WebSocket 消息：文本帧走 `pretty_json`（方便看里面
+/// 的 JSON payload），二进制帧只打印长度，控制帧（close/ping/pong）打印摘要。
+fn print_ws_message(dir: Direction, opcode: u8, payload: &[u8]) {
+    let arrow = match dir {
+        Direction::ClientToServer => "\x1b[1;32m▶ WS\x1b[0m",
+        Direction::ServerToClient => "\x1b[1;34m◀ WS\x1b[0m",
+    };
+    match opcode {
+        0x1 => {
+            println!("{} 文本帧:", arrow);
+            pretty_json(&String::from_utf8_lossy(payload), "    ");
+        }
+        0x2 => println!("{} 二进制帧: {} 字节", arrow, payload.len()),
+        0x8 => println!("{} CLOSE ({} 字节)", arrow, payload.len()),
+        0x9 => println!("{} PING ({} 字节)", arrow, payload.len()),
+        0xA => println!("{} PONG ({} 字节)", arrow, payload.len()),
+        other => println!("{} 未知 opcode {} ({} 字节)", arrow, other, payload.len()),
+    }
+}
+
+#[cfg(test)]
+mod ws_frame_tests {
+    use super::decode_ws_frame;
+
+    #[test]
+    fn decodes_unmasked_text_frame() {
+        let frame = [0x81, 0x05, b'h', b'e', b'l', b'l', b'o'];
+        let (parsed, consumed) = decode_ws_frame(&frame).unwrap();
+        assert!(parsed.fin);
+        assert_eq!(parsed.opcode, 0x1);
+        assert_eq!(parsed.payload, b"hello");
+        assert_eq!(consumed, frame.len());
+    }
+
+    #[test]
+    fn decodes_masked_frame_and_unmasks_payload() {
+        let key = [0x01, 0x02, 0x03, 0x04];
+        let raw = b"hi";
+        let masked: Vec<u8> = raw
+            .iter()
+            .enumerate()
+            .map(|(i, b)| b ^ key[i % 4])
+            .collect();
+        let mut frame = vec![0x81, 0x80 | 2];
+        frame.extend_from_slice(&key);
+        frame.extend_from_slice(&masked);
+        let (parsed, consumed) = decode_ws_frame(&frame).unwrap();
+        assert_eq!(parsed.payload, raw);
+        assert_eq!(consumed, frame.len());
+    }
+
+    #[test]
+    fn incomplete_frame_returns_none() {
+        let frame = [0x81, 0x05, b'h', b'e'];
+        assert!(decode_ws_frame(&frame).is_none());
+    }
+
+    #[test]
+    fn huge_extended_length_does_not_panic() {
+        let mut frame = vec![0x81, 127];
+        frame.extend_from_slice(&u64::MAX.to_be_bytes());
+        assert!(decode_ws_frame(&frame).is_none());
+    }
+}
+
+/// 解析 `data` 开头尽可能多的完整 chunk 帧（`<hex-size>\r\n<data>\r\n`），把解码
+/// 出来的内容追加进 `out`。遇到不完整的帧（大小行或数据还没收全）就停下，把剩余
+/// 字节留给下一次调用，这样跨多个 TCP 分段到达的 chunk 也能正确拼起来。
+/// 返回 `(consumed, finished)`，`finished` 为 `true` 表示已经读到终止块
+/// `0\r\n\r\n`（trailer header 会被跳过，不做解析）。
+fn decode_chunked(data: &[u8], out: &mut Vec<u8>) -> (usize, bool) {
+    let mut offset = 0;
+    loop {
+        let Some(line_len) = find_crlf(&data[offset..]) else {
+            break;
+        };
+        let size_str = String::from_utf8_lossy(&data[offset..offset + line_len]);
+        let size_str = size_str.split(';').next().unwrap_or("").trim();
+        let Ok(size) = usize::from_str_radix(size_str, 16) else {
+            break;
+        };
+        let chunk_start = offset + line_len + 2;
+
+        if size == 0 {
+            let mut trailer_end = chunk_start;
+            loop {
+                let Some(tl) = find_crlf(&data[trailer_end..]) else {
+                    return (offset, false);
+                };
+                trailer_end += tl + 2;
+                if tl == 0 {
+                    break;
+                }
+            }
+            return (trailer_end, true);
+        }
+
+        let Some(chunk_end) = chunk_start.checked_add(size).and_then(|v| v.checked_add(2)) else {
+            break;
+        };
+        if data.len() < chunk_end {
+            break;
+        }
+        out.extend_from_slice(&data[chunk_start..chunk_start + size]);
+        offset = chunk_end;
+    }
+    (offset, false)
+}
+
+fn find_crlf(data: &[u8]) -> Option<usize> {
+    data.windows(2).position(|w| w == b"\r\n")
+}
+
+#[cfg(test)]
+mod chunked_tests {
+    use super::decode_chunked;
+
+    #[test]
+    fn decodes_single_chunk_and_terminator() {
+        let mut out = Vec::new();
+        let (consumed, finished) = decode_chunked(b"5\r\nhello\r\n0\r\n\r\n", &mut out);
+        assert_eq!(consumed, "5\r\nhello\r\n0\r\n\r\n".len());
+        assert!(finished);
+        assert_eq!(out, b"hello");
+    }
+
+    #[test]
+    fn stops_on_chunk_split_across_segments() {
+        let mut out = Vec::new();
+        let (consumed, finished) = decode_chunked(b"5\r\nhel", &mut out);
+        assert_eq!(consumed, 0);
+        assert!(!finished);
+        assert!(out.is_empty());
+    }
+
+    #[test]
+    fn rejects_oversized_hex_length_without_overflow_panic() {
+        let mut out = Vec::new();
+        let (consumed, finished) = decode_chunked(b"ffffffffffffffff\r\nhello\r\n", &mut out);
+        assert_eq!(consumed, 0);
+        assert!(!finished);
+        assert!(out.is_empty());
+    }
+}
+
+/// 按 `encoding` 解压 body 再转成文本；解压失败（比如截断的抓包）时退化成
+/// `[compressed N bytes]` 提示，而不是把压缩后的二进制直接丢给 `pretty_json`。
+fn decode_body(raw: &[u8], encoding: ContentEncoding) -> String {
+    if raw.is_empty() {
+        return String::new();
+    }
+    let decoded = match encoding {
+        ContentEncoding::Identity => None,
+        ContentEncoding::Gzip => decompress_with(raw, |r| GzDecoder::new(r)),
+        ContentEncoding::Deflate => decompress_with(raw, |r| DeflateDecoder::new(r)),
+        ContentEncoding::Brotli => decompress_brotli(raw),
+    };
+    match decoded {
+        Some(text) => text,
+        None if encoding == ContentEncoding::Identity => String::from_utf8_lossy(raw).to_string(),
+        None => format!("[compressed {} bytes]", raw.len()),
+    }
+}
+
+fn decompress_with<'a, D: std::io::Read>(
+    raw: &'a [u8],
+    make_decoder: impl FnOnce(&'a [u8]) -> D,
+) -> Option<String> {
+    let mut out = String::new();
+    make_decoder(raw).read_to_string(&mut out).ok()?;
+    Some(out)
+}
+
+fn decompress_brotli(raw: &[u8]) -> Option<String> {
+    let mut out = String::new();
+    brotli::Decompressor::new(raw, 4096)
+        .read_to_string(&mut out)
+        .ok()?;
+    Some(out)
+}
+
+#[cfg(test)]
+mod decode_body_tests {
+    use super::{decode_body, ContentEncoding};
+
+    #[test]
+    fn falls_back_to_compressed_marker_on_garbage_gzip() {
+        let raw = b"this is not actually gzip data";
+        let out = decode_body(raw, ContentEncoding::Gzip);
+        assert_eq!(out, format!("[compressed {} bytes]", raw.len()));
+    }
+
+    #[test]
+    fn falls_back_to_compressed_marker_on_garbage_brotli() {
+        let raw = b"this is not actually brotli data either";
+        let out = decode_body(raw, ContentEncoding::Brotli);
+        assert_eq!(out, format!("[compressed {} bytes]", raw.len()));
+    }
+
+    #[test]
+    fn identity_passes_bytes_through_as_text() {
+        let out = decode_body(b"hello world", ContentEncoding::Identity);
+        assert_eq!(out, "hello world");
+    }
+}
+
+fn output_transaction(
+    tx: &mut HttpTransaction,
+    stream: &StreamBuffer,
+    server_host: Option<&str>,
+    format: OutputFormat,
+) {
+    match format {
+        OutputFormat::Pretty => output_pretty(tx, server_host),
+        OutputFormat::Json => output_json(tx, stream, server_host, false),
+        OutputFormat::Jsonl => output_json(tx, stream, server_host, true),
+    }
+}
+
+/// 把一个事务编成一个 JSON 对象：流五元组、方法/路径/状态码、请求/响应头
+/// （展开成 map）、解码后的 body、SSE 事件列表。`compact` 为 `true` 时输出
+/// 单行紧凑 JSON（给 `jsonl` 用），否则带缩进打印并在对象之间留空行（给
+/// `json` 用）。SSE 事务每来一批新事件就会被再调用一次，这里只把上次调用
+/// 之后新出现的事件（`res_events_emitted..`）放进 `sse_events`，避免同一条
+/// 事件在每次增量输出里被重复吐出来。
+fn output_json(
+    tx: &mut HttpTransaction,
+    stream: &StreamBuffer,
+    server_host: Option<&str>,
+    compact: bool,
+) {
+    let timestamp_ms = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or(0);
+    let new_events = &tx.res_body_events[tx.res_events_emitted..];
+    let value = serde_json::json!({
+        "timestamp": timestamp_ms,
+        "flow": {
+            "src_ip": stream.client_addr.to_string(),
+            "src_port": stream.client_port,
+            "dst_ip": stream.server_addr.to_string(),
+            "dst_port": stream.server_port,
+        },
+        "dns_name": server_host,
+        "method": tx.method,
+        "path": tx.path,
+        "status": tx.status,
+        "req_headers": tx.req_headers,
+        "res_headers": tx.res_headers,
+        "req_body": decode_body(&tx.req_body, tx.req_encoding),
+        "res_body": decode_body(&tx.res_body_raw, tx.res_encoding),
+        "sse_events": new_events,
+    });
+    tx.res_events_emitted = tx.res_body_events.len();
+    if compact {
+        println!("{}", value);
+    } else if let Ok(pretty) = serde_json::to_string_pretty(&value) {
+        println!("{}\n", pretty);
+    }
+}
+
+fn output_pretty(tx: &mut HttpTransaction, server_host: Option<&str>) {
+    if tx.is_sse {
+        println!(
+            "\n\x1b[1;35m[SSE 会话更新 - 累计事件: {}]\x1b[0m",
+            tx.res_body_events.len()
+        );
+        if let Some(host) = server_host {
+            println!("  \x1b[90m[DNS] 服务端域名: {}\x1b[0m", host);
+        }
+        println!("{}", tx.req_header);
+        if !tx.req_body.is_empty() {
+            println!("  \x1b[90m[Request Body]\x1b[0m");
+            pretty_json(&decode_body(&tx.req_body, tx.req_encoding), "    ");
+        }
+        println!("\n{}", tx.res_header);
+        for (i, event) in tx.res_body_events.iter().enumerate() {
+            if event.starts_with(": ping") {
+                println!("    \x1b[90m[{}] {}\x1b[0m", i + 1, event);
+            } else {
+                println!("    \x1b[33m[Event {}]\x1b[0m", i + 1);
+                pretty_json(event, "      ");
+            }
+        }
+        println!("\x1b[1;35m{}\x1b[0m", "-".repeat(50));
+    } else if !tx.req_printed {
+        println!("\n\x1b[1;36m==================== TRANSACTION ====================\x1b[0m");
+        if let Some(host) = server_host {
+            println!("  \x1b[90m[DNS] 服务端域名: {}\x1b[0m", host);
+        }
+        println!("{}", tx.req_header);
+        if !tx.req_body.is_empty() {
+            println!("  \x1b[90m[Request Body]\x1b[0m");
+            pretty_json(&decode_body(&tx.req_body, tx.req_encoding), "    ");
+        }
+        println!("\n{}", tx.res_header);
+        if !tx.res_body_raw.is_empty() {
+            println!("  \x1b[90m[Response Body]\x1b[0m");
+            pretty_json(&decode_body(&tx.res_body_raw, tx.res_encoding), "    ");
+        }
+        println!("\x1b[1;36m=====================================================\x1b[0m\n");
+        tx.req_printed = true;
+    }
+}
+
+fn pretty_json(raw: &str, indent: &str) {
+    let clean = if raw.starts_with("data: ") {
+        raw.strip_prefix("data: ").unwrap_or(raw).trim()
+    } else {
+        raw.trim()
+    };
+    if let Ok(v) = serde_json::from_str::<serde_json::Value>(clean) {
+        if let Ok(p) = serde_json::to_string_pretty(&v) {
+            for l in p.lines() {
+                println!("{}{}", indent, l);
+            }
+            return;
+        }
+    }
+    println!("{}{}", indent, raw);
+}
@@ -1,9 +1,27 @@
+//! parsehttp - 基于 libpcap 的 HTTP/HTTPS 流量离线与实时分析工具
+//!
+//! 按方向拆分的 TCP 流交给 `app_layer::Registry` 探测协议，目前能认出 HTTP（含
+//! chunked/gzip-deflate-br 解码、`Upgrade: websocket` 之后的帧解析）和 Redis
+//! RESP；UDP 53 端口的 DNS 报文单独解析，解出来的域名关联到后续的 HTTP 事务上。
+//!
+//! 按序号重组 TCP（而非仅按到达顺序拼接）不在计划内：当前依赖 libpcap 给到的
+//! 抓包顺序，真实丢包/乱序场景下的重组留给更底层的工具（或未来的 smoltcp 集成）处理。
+
+mod app_layer;
+mod dns;
+mod http;
+mod redis;
+
+use app_layer::{AppLayerParser, Registry, StreamBuffer};
 use clap::{Parser, Subcommand};
+use http::OutputFormat;
 use pcap::{Capture, Device, Linktype};
 use pnet::packet::ethernet::{EtherTypes, EthernetPacket};
+use pnet::packet::ip::{IpNextHeaderProtocol, IpNextHeaderProtocols};
 use pnet::packet::ipv4::Ipv4Packet;
 use pnet::packet::ipv6::Ipv6Packet;
 use pnet::packet::tcp::TcpPacket;
+use pnet::packet::udp::UdpPacket;
 use pnet::packet::Packet;
 use std::collections::HashMap;
 use std::net::IpAddr;
@@ -13,6 +31,11 @@ use std::net::IpAddr;
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// 事务输出格式：pretty（彩色终端，默认）、json（带缩进，方便人读）、
+    /// jsonl（一行一个对象，方便接 jq / 日志管道）
+    #[arg(long, value_enum, global = true, default_value = "pretty")]
+    format: OutputFormat,
 }
 
 #[derive(Subcommand)]
@@ -57,34 +80,17 @@ impl FlowKey {
     }
 }
 
-#[derive(PartialEq, Debug)]
-enum TransactionState {
-    RequestBody,
-    ResponseHeader,
-    ResponseBody,
-}
-
-struct HttpTransaction {
-    req_header: String,
-    req_body: Vec<u8>,
-    expected_req_len: usize,
-    res_header: String,
-    res_body_raw: Vec<u8>,
-    res_body_events: Vec<String>,
-    expected_res_len: usize,
-    is_sse: bool,
-    state: TransactionState,
-    req_printed: bool,
-}
-
-struct StreamBuffer {
-    data: Vec<u8>,
-    current_tx: Option<HttpTransaction>,
+/// 一条 TCP 流的全部状态：方向缓冲区，加上（一旦探测出协议就）接管它的解析器。
+struct FlowState {
+    stream: StreamBuffer,
+    parser: Option<Box<dyn AppLayerParser>>,
 }
 
 fn main() {
     let cli = Cli::parse();
-    let mut streams: HashMap<FlowKey, StreamBuffer> = HashMap::new();
+    let mut flows: HashMap<FlowKey, FlowState> = HashMap::new();
+    let mut dns_names: HashMap<IpAddr, String> = HashMap::new();
+    let registry = Registry::new(cli.format);
 
     match cli.command {
         Commands::List => {
@@ -95,7 +101,7 @@ fn main() {
         }
         Commands::File { path } => {
             let cap = Capture::from_file(path).expect("无法打开文件");
-            run_analysis(cap, &mut streams);
+            run_analysis(cap, &mut flows, &mut dns_names, &registry);
         }
         Commands::Live { interface, filter } => {
             let device = Device::list()
@@ -114,14 +120,16 @@ fn main() {
             // 提示：在 lo0 上抓包，filter 建议直接用 "port 4081"
             cap.filter(&filter, true).unwrap();
             println!("\x1b[1;33m正在监听: {} (BPF: {})\x1b[0m", interface, filter);
-            run_analysis(cap, &mut streams);
+            run_analysis(cap, &mut flows, &mut dns_names, &registry);
         }
     }
 }
 
 fn run_analysis<T: pcap::Activated>(
     mut cap: Capture<T>,
-    streams: &mut HashMap<FlowKey, StreamBuffer>,
+    flows: &mut HashMap<FlowKey, FlowState>,
+    dns_names: &mut HashMap<IpAddr, String>,
+    registry: &Registry,
 ) {
     let link_type = cap.get_datalink();
 
@@ -134,24 +142,58 @@ fn run_analysis<T: pcap::Activated>(
             None
         };
 
-        if let Some((src, dst, ip_payload)) = parsed {
-            if let Some(tcp) = TcpPacket::new(&ip_payload) {
-                if tcp.payload().is_empty() {
-                    continue;
+        let Some((src, dst, proto, ip_payload)) = parsed else {
+            continue;
+        };
+
+        if proto == IpNextHeaderProtocols::Tcp {
+            let Some(tcp) = TcpPacket::new(&ip_payload) else {
+                continue;
+            };
+            if tcp.payload().is_empty() {
+                continue;
+            }
+            let key = FlowKey::new(src, tcp.get_source(), dst, tcp.get_destination());
+            let src_port = tcp.get_source();
+            let dst_port = tcp.get_destination();
+            let flow = flows.entry(key).or_insert_with(|| FlowState {
+                stream: StreamBuffer::new(src, src_port, dst, dst_port),
+                parser: None,
+            });
+            let dir = if src == flow.stream.client_addr && src_port == flow.stream.client_port {
+                app_layer::Direction::ClientToServer
+            } else {
+                app_layer::Direction::ServerToClient
+            };
+            match dir {
+                app_layer::Direction::ClientToServer => {
+                    flow.stream.c2s.extend_from_slice(tcp.payload())
                 }
-                let key = FlowKey::new(src, tcp.get_source(), dst, tcp.get_destination());
-                let stream = streams.entry(key).or_insert(StreamBuffer {
-                    data: Vec::new(),
-                    current_tx: None,
-                });
-                stream.data.extend_from_slice(tcp.payload());
-                process_stream(stream);
+                app_layer::Direction::ServerToClient => {
+                    flow.stream.s2c.extend_from_slice(tcp.payload())
+                }
+            }
+
+            if flow.parser.is_none() {
+                flow.parser = registry.probe(flow.stream.buffer(dir));
+            }
+            if let Some(parser) = flow.parser.as_mut() {
+                parser.parse(dir, &mut flow.stream, dns_names);
+            }
+        } else if proto == IpNextHeaderProtocols::Udp {
+            let Some(udp) = UdpPacket::new(&ip_payload) else {
+                continue;
+            };
+            if udp.get_source() == 53 || udp.get_destination() == 53 {
+                handle_dns_packet(udp.payload(), dns_names);
             }
         }
     }
 }
 
-fn parse_ethernet(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>)> {
+fn parse_ethernet(
+    packet: &pcap::Packet,
+) -> Option<(IpAddr, IpAddr, IpNextHeaderProtocol, Vec<u8>)> {
     let eth = EthernetPacket::new(packet.data)?;
     match eth.get_ethertype() {
         EtherTypes::Ipv4 => {
@@ -159,6 +201,7 @@ fn parse_ethernet(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>)> {
             Some((
                 ip.get_source().into(),
                 ip.get_destination().into(),
+                ip.get_next_level_protocol(),
                 ip.payload().to_vec(),
             ))
         }
@@ -167,6 +210,7 @@ fn parse_ethernet(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>)> {
             Some((
                 ip.get_source().into(),
                 ip.get_destination().into(),
+                ip.get_next_header(),
                 ip.payload().to_vec(),
             ))
         }
@@ -174,7 +218,9 @@ fn parse_ethernet(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>)> {
     }
 }
 
-fn parse_null_loopback(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>)> {
+fn parse_null_loopback(
+    packet: &pcap::Packet,
+) -> Option<(IpAddr, IpAddr, IpNextHeaderProtocol, Vec<u8>)> {
     if packet.data.len() < 4 {
         return None;
     }
@@ -203,6 +249,7 @@ fn parse_null_loopback(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>
             Some((
                 ip.get_source().into(),
                 ip.get_destination().into(),
+                ip.get_next_level_protocol(),
                 ip.payload().to_vec(),
             ))
         }
@@ -212,6 +259,7 @@ fn parse_null_loopback(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>
             Some((
                 ip.get_source().into(),
                 ip.get_destination().into(),
+                ip.get_next_header(),
                 ip.payload().to_vec(),
             ))
         }
@@ -219,177 +267,52 @@ fn parse_null_loopback(packet: &pcap::Packet) -> Option<(IpAddr, IpAddr, Vec<u8>
     }
 }
 
-fn process_stream(stream: &mut StreamBuffer) {
-    loop {
-        let mut headers = [httparse::EMPTY_HEADER; 64];
-        let mut consumed = 0;
+/// 解析一个 DNS 报文并打印查询/应答；同时把 A/AAAA 记录里解出来的
+/// IP -> 域名 映射记进 `dns_names`，供后续 HTTP 事务输出时查找服务端域名。
+fn handle_dns_packet(payload: &[u8], dns_names: &mut HashMap<IpAddr, String>) {
+    let Some(pkt) = dns::parse(payload) else {
+        return;
+    };
 
-        if stream.current_tx.is_none() {
-            let mut req = httparse::Request::new(&mut headers);
-            if let Ok(httparse::Status::Complete(amt)) = req.parse(&stream.data) {
-                let mut content_len = 0;
-                let mut h_str = String::new();
-                for h in req.headers.iter() {
-                    let name = h.name.to_lowercase();
-                    let val = String::from_utf8_lossy(h.value);
-                    if name == "content-length" {
-                        content_len = val.parse().unwrap_or(0);
-                    }
-                    h_str.push_str(&format!("  {}: {}\n", h.name, val));
-                }
-                stream.current_tx = Some(HttpTransaction {
-                    req_header: format!(
-                        "\x1b[1;32m▶ REQUEST: {} {}\x1b[0m\n{}",
-                        req.method.unwrap_or(""),
-                        req.path.unwrap_or(""),
-                        h_str
-                    ),
-                    req_body: Vec::new(),
-                    expected_req_len: content_len,
-                    res_header: String::new(),
-                    res_body_raw: Vec::new(),
-                    res_body_events: Vec::new(),
-                    expected_res_len: 0,
-                    is_sse: false,
-                    req_printed: false,
-                    state: if content_len > 0 {
-                        TransactionState::RequestBody
-                    } else {
-                        TransactionState::ResponseHeader
-                    },
-                });
-                consumed = amt;
-            }
-        } else if let Some(tx) = &mut stream.current_tx {
-            match tx.state {
-                TransactionState::RequestBody => {
-                    let take =
-                        std::cmp::min(tx.expected_req_len - tx.req_body.len(), stream.data.len());
-                    tx.req_body.extend_from_slice(&stream.data[..take]);
-                    consumed = take;
-                    if tx.req_body.len() >= tx.expected_req_len {
-                        tx.state = TransactionState::ResponseHeader;
-                    }
-                }
-                TransactionState::ResponseHeader => {
-                    let mut res = httparse::Response::new(&mut headers);
-                    if let Ok(httparse::Status::Complete(amt)) = res.parse(&stream.data) {
-                        let mut clen = 0;
-                        let mut h_str = String::new();
-                        for h in res.headers.iter() {
-                            let name = h.name.to_lowercase();
-                            let val = String::from_utf8_lossy(h.value);
-                            if name == "content-type" && val.contains("text/event-stream") {
-                                tx.is_sse = true;
-                            }
-                            if name == "content-length" {
-                                clen = val.parse().unwrap_or(0);
-                            }
-                            h_str.push_str(&format!("  {}: {}\n", h.name, val));
-                        }
-                        tx.res_header = format!(
-                            "\x1b[1;34m◀ RESPONSE: {} {}\x1b[0m\n{}",
-                            res.code.unwrap_or(0),
-                            res.reason.unwrap_or(""),
-                            h_str
-                        );
-                        tx.expected_res_len = clen;
-                        tx.state = TransactionState::ResponseBody;
-                        consumed = amt;
-                        if !tx.is_sse && tx.expected_res_len == 0 {
-                            output_transaction(tx);
-                            stream.current_tx = None;
-                        }
-                    }
-                }
-                TransactionState::ResponseBody => {
-                    if tx.is_sse {
-                        let body = String::from_utf8_lossy(&stream.data).to_string();
-                        let mut new_e = false;
-                        for e in body.split("\n\n") {
-                            if !e.trim().is_empty() {
-                                tx.res_body_events.push(e.trim().to_string());
-                                new_e = true;
-                            }
-                        }
-                        consumed = stream.data.len();
-                        if new_e {
-                            output_transaction(tx);
-                        }
-                    } else {
-                        let take = std::cmp::min(
-                            tx.expected_res_len - tx.res_body_raw.len(),
-                            stream.data.len(),
-                        );
-                        tx.res_body_raw.extend_from_slice(&stream.data[..take]);
-                        consumed = take;
-                        if tx.res_body_raw.len() >= tx.expected_res_len {
-                            output_transaction(tx);
-                            stream.current_tx = None;
-                        }
-                    }
-                }
-            }
-        }
-        if consumed > 0 {
-            stream.data.drain(..consumed);
-        } else {
-            break;
-        }
+    println!(
+        "\n\x1b[1;35m● DNS {} (id={})\x1b[0m",
+        if pkt.is_response { "RESPONSE" } else { "QUERY" },
+        pkt.id
+    );
+    for q in &pkt.questions {
+        println!("  {} {}", dns::record_type_name(q.qtype), q.name);
     }
-}
-
-fn output_transaction(tx: &mut HttpTransaction) {
-    if tx.is_sse {
+    if pkt.nscount > 0 || pkt.arcount > 0 {
         println!(
-            "\n\x1b[1;35m[SSE 会话更新 - 累计事件: {}]\x1b[0m",
-            tx.res_body_events.len()
+            "  \x1b[90m[{} authority / {} additional 记录未解码]\x1b[0m",
+            pkt.nscount, pkt.arcount
         );
-        println!("{}", tx.req_header);
-        if !tx.req_body.is_empty() {
-            println!("  \x1b[90m[Request Body]\x1b[0m");
-            pretty_json(&String::from_utf8_lossy(&tx.req_body), "    ");
-        }
-        println!("\n{}", tx.res_header);
-        for (i, event) in tx.res_body_events.iter().enumerate() {
-            if event.starts_with(": ping") {
-                println!("    \x1b[90m[{}] {}\x1b[0m", i + 1, event);
-            } else {
-                println!("    \x1b[33m[Event {}]\x1b[0m", i + 1);
-                pretty_json(event, "      ");
-            }
-        }
-        println!("\x1b[1;35m{}\x1b[0m", "-".repeat(50));
-    } else if !tx.req_printed {
-        println!("\n\x1b[1;36m==================== TRANSACTION ====================\x1b[0m");
-        println!("{}", tx.req_header);
-        if !tx.req_body.is_empty() {
-            println!("  \x1b[90m[Request Body]\x1b[0m");
-            pretty_json(&String::from_utf8_lossy(&tx.req_body), "    ");
-        }
-        println!("\n{}", tx.res_header);
-        if !tx.res_body_raw.is_empty() {
-            println!("  \x1b[90m[Response Body]\x1b[0m");
-            pretty_json(&String::from_utf8_lossy(&tx.res_body_raw), "    ");
-        }
-        println!("\x1b[1;36m=====================================================\x1b[0m\n");
-        tx.req_printed = true;
     }
-}
-
-fn pretty_json(raw: &str, indent: &str) {
-    let clean = if raw.starts_with("data: ") {
-        raw.strip_prefix("data: ").unwrap_or(raw).trim()
-    } else {
-        raw.trim()
-    };
-    if let Ok(v) = serde_json::from_str::<serde_json::Value>(clean) {
-        if let Ok(p) = serde_json::to_string_pretty(&v) {
-            for l in p.lines() {
-                println!("{}{}", indent, l);
+    for a in &pkt.answers {
+        match &a.data {
+            dns::DnsRecordData::A(ip) => {
+                println!("    -> A {} = {} (ttl {})", a.name, ip, a.ttl);
+                dns_names.insert(IpAddr::V4(*ip), a.name.clone());
+            }
+            dns::DnsRecordData::Aaaa(ip) => {
+                println!("    -> AAAA {} = {} (ttl {})", a.name, ip, a.ttl);
+                dns_names.insert(IpAddr::V6(*ip), a.name.clone());
+            }
+            dns::DnsRecordData::Cname(target) => {
+                println!("    -> CNAME {} = {} (ttl {})", a.name, target, a.ttl);
+            }
+            dns::DnsRecordData::Ns(target) => {
+                println!("    -> NS {} = {} (ttl {})", a.name, target, a.ttl);
+            }
+            dns::DnsRecordData::Soa { mname, rname } => {
+                println!(
+                    "    -> SOA {} mname={} rname={} (ttl {})",
+                    a.name, mname, rname, a.ttl
+                );
+            }
+            dns::DnsRecordData::Other(rtype) => {
+                println!("    -> type {} {} (ttl {})", rtype, a.name, a.ttl);
             }
-            return;
         }
     }
-    println!("{}{}", indent, raw);
 }
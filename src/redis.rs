@@ -0,0 +1,144 @@
+//! 简化的 Redis RESP 解析器，按类型前缀把字节解码成值并打印出来。
+
+use crate::app_layer::{AppLayerParser, Direction, ParseResult, StreamBuffer};
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+#[derive(Default)]
+pub struct RedisParser;
+
+impl AppLayerParser for RedisParser {
+    fn name(&self) -> &'static str {
+        "redis"
+    }
+
+    fn probe(&self, data: &[u8]) -> bool {
+        // RESP 的多条批量命令（客户端发送请求的标准格式）总是以 `*<count>\r\n`
+        // 开头，这个前缀基本不会跟别的协议撞车，拿来做探测足够了。
+        data.first() == Some(&b'*')
+    }
+
+    fn parse(
+        &mut self,
+        dir: Direction,
+        stream: &mut StreamBuffer,
+        _dns_names: &HashMap<IpAddr, String>,
+    ) -> ParseResult {
+        let label = match dir {
+            Direction::ClientToServer => "\x1b[1;32m▶ REDIS 命令\x1b[0m",
+            Direction::ServerToClient => "\x1b[1;34m◀ REDIS 回复\x1b[0m",
+        };
+        let buf = match dir {
+            Direction::ClientToServer => &mut stream.c2s,
+            Direction::ServerToClient => &mut stream.s2c,
+        };
+
+        let mut progressed = false;
+        while let Some((value, consumed)) = parse_value(buf) {
+            println!("{}: {}", label, format_value(&value));
+            buf.drain(..consumed);
+            progressed = true;
+        }
+
+        if progressed {
+            ParseResult::Consumed
+        } else {
+            ParseResult::Incomplete
+        }
+    }
+}
+
+enum RespValue {
+    Simple(String),
+    Error(String),
+    Integer(i64),
+    Bulk(Option<Vec<u8>>),
+    Array(Option<Vec<RespValue>>),
+}
+
+fn format_value(v: &RespValue) -> String {
+    match v {
+        RespValue::Simple(s) => s.clone(),
+        RespValue::Error(s) => format!("ERR {}", s),
+        RespValue::Integer(n) => n.to_string(),
+        RespValue::Bulk(None) => "(nil)".to_string(),
+        RespValue::Bulk(Some(b)) => String::from_utf8_lossy(b).to_string(),
+        RespValue::Array(None) => "(nil array)".to_string(),
+        RespValue::Array(Some(items)) => {
+            let parts: Vec<String> = items.iter().map(format_value).collect();
+            format!("[{}]", parts.join(" "))
+        }
+    }
+}
+
+/// 尝试从 `data` 开头解析一个完整的 RESP 值；数据不够就返回 `None`，调用方把
+/// 剩余字节留在缓冲区里等下一批数据到达再重试。
+fn parse_value(data: &[u8]) -> Option<(RespValue, usize)> {
+    let (&tag, rest) = data.split_first()?;
+    match tag {
+        b'+' => {
+            let (line, len) = read_line(rest)?;
+            Some((
+                RespValue::Simple(String::from_utf8_lossy(line).to_string()),
+                1 + len,
+            ))
+        }
+        b'-' => {
+            let (line, len) = read_line(rest)?;
+            Some((
+                RespValue::Error(String::from_utf8_lossy(line).to_string()),
+                1 + len,
+            ))
+        }
+        b':' => {
+            let (line, len) = read_line(rest)?;
+            let n = String::from_utf8_lossy(line).trim().parse().ok()?;
+            Some((RespValue::Integer(n), 1 + len))
+        }
+        b'$' => {
+            let (line, len) = read_line(rest)?;
+            let size: i64 = String::from_utf8_lossy(line).trim().parse().ok()?;
+            if size < 0 {
+                return Some((RespValue::Bulk(None), 1 + len));
+            }
+            let size = size as usize;
+            let body_start = 1 + len;
+            if data.len() < body_start + size + 2 {
+                return None;
+            }
+            let body = data[body_start..body_start + size].to_vec();
+            Some((RespValue::Bulk(Some(body)), body_start + size + 2))
+        }
+        b'*' => {
+            let (line, len) = read_line(rest)?;
+            let count: i64 = String::from_utf8_lossy(line).trim().parse().ok()?;
+            if count < 0 {
+                return Some((RespValue::Array(None), 1 + len));
+            }
+            // 每个数组元素至少占 1 字节，用缓冲区总长度卡一下 count 的上限：一个
+            // 构造出来的巨大 count（比如 `*9223372036854775807\r\n`）不会先跑去
+            // `Vec::with_capacity` 里直接把进程 panic 掉，而是老老实实地当成
+            // "数据还没收全" 处理，等下一批数据来了再重试。
+            if count as u64 > data.len() as u64 {
+                return None;
+            }
+            let count = count as usize;
+            let mut offset = 1 + len;
+            let mut items = Vec::with_capacity(count);
+            for _ in 0..count {
+                let (value, consumed) = parse_value(&data[offset..])?;
+                items.push(value);
+                offset += consumed;
+            }
+            Some((RespValue::Array(Some(items)), offset))
+        }
+        _ => None,
+    }
+}
+
+/// 读到 `\r\n` 为止的一行（不含结尾的 `\r\n`），返回行内容和连同 `\r\n` 在内一共
+/// 消费了多少字节。
+fn read_line(data: &[u8]) -> Option<(&[u8], usize)> {
+    let pos = data.windows(2).position(|w| w == b"\r\n")?;
+    Some((&data[..pos], pos + 2))
+}